@@ -1,3 +1,16 @@
+#![cfg_attr(feature = "core_io", no_std)]
+
+#[cfg(feature = "core_io")]
+extern crate alloc;
+
+#[cfg(feature = "core_io")]
+use alloc::{string::String, vec::Vec};
+
+#[cfg(feature = "core_io")]
+use core_io as io;
+#[cfg(not(feature = "core_io"))]
+use std::io as io;
+
 pub mod ffi {
     #[cfg(not(target_os = "linux"))]
     #[link(name = "iconv")]
@@ -26,15 +39,46 @@ pub mod ffi {
 }
 
 use libc::size_t;
-use std::io::{BufRead, Read, Write};
+use io::{BufRead, Read, Write};
+#[cfg(not(feature = "core_io"))]
+use std::io::{IoSlice, IoSliceMut};
 
 use dyn_buf::VecBuf;
 
 const MIN_WRITE: usize = 4096;
 
+/// Fetch the errno left behind by the last failing libc call.
+#[cfg(not(feature = "core_io"))]
+fn last_errno() -> i32 {
+    std::io::Error::last_os_error().raw_os_error().unwrap()
+}
+
+#[cfg(feature = "core_io")]
+fn last_errno() -> i32 {
+    unsafe { *libc::__errno_location() }
+}
+
 /// The representation of a iconv converter
 pub struct Iconv {
     cd: ffi::iconv_t,
+    replacement: Option<Vec<u8>>,
+    /// Set for `//TRANSLIT`/`//IGNORE` converters: glibc reports a successful
+    /// transliteration or drop as `-1`/`EILSEQ` once it has made progress, so
+    /// that case has to be treated as success rather than a hard error.
+    lenient: bool,
+}
+
+/// How a converter should handle characters that cannot be represented in the target encoding.
+#[derive(Debug, Clone, Copy)]
+pub enum OnError {
+    /// Fail the conversion with [`IconvError::InvalidInput`], the default `iconv` behavior.
+    Strict,
+    /// Substitute a similar-looking character in the target encoding (`//TRANSLIT`).
+    Transliterate,
+    /// Drop unconvertible characters from the output (`//IGNORE`).
+    Ignore,
+    /// Substitute `char` for unconvertible or malformed input, always making forward progress.
+    Replace(char),
 }
 
 #[derive(Debug)]
@@ -47,27 +91,30 @@ pub enum IconvError {
 }
 
 impl IconvError {
-    pub fn into_io_error(self) -> std::io::Error {
+    pub fn into_io_error(self) -> io::Error {
         match self {
-            IconvError::OsError(e) => std::io::Error::from_raw_os_error(e),
+            IconvError::OsError(e) => io::Error::from_raw_os_error(e),
             IconvError::ConversionNotSupport => {
-                std::io::Error::new(std::io::ErrorKind::Unsupported, self)
+                io::Error::new(io::ErrorKind::Unsupported, self)
             }
             IconvError::NotSufficientOutput => {
-                std::io::Error::new(std::io::ErrorKind::InvalidInput, self)
+                io::Error::new(io::ErrorKind::InvalidInput, self)
             }
-            IconvError::InvalidInput => std::io::Error::new(std::io::ErrorKind::InvalidData, self),
+            IconvError::InvalidInput => io::Error::new(io::ErrorKind::InvalidData, self),
             IconvError::IncompleteInput => {
-                std::io::Error::new(std::io::ErrorKind::InvalidInput, self)
+                io::Error::new(io::ErrorKind::InvalidInput, self)
             }
         }
     }
 }
 
-impl std::fmt::Display for IconvError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for IconvError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
+            #[cfg(not(feature = "core_io"))]
             IconvError::OsError(e) => write!(f, "{}", std::io::Error::from_raw_os_error(*e)),
+            #[cfg(feature = "core_io")]
+            IconvError::OsError(e) => write!(f, "os error {}", e),
             IconvError::ConversionNotSupport => {
                 write!(f, "The conversion is not supported by the implementation")
             }
@@ -86,11 +133,29 @@ impl std::fmt::Display for IconvError {
     }
 }
 
+#[cfg(not(feature = "core_io"))]
 impl std::error::Error for IconvError {}
+#[cfg(feature = "core_io")]
+impl core::error::Error for IconvError {}
 
 /// convert `input` from `from_encoding` to `to_encoding`
 pub fn iconv(input: &[u8], from_encoding: &str, to_encoding: &str) -> Result<Vec<u8>, IconvError> {
-    let mut c = Iconv::new(from_encoding, to_encoding)?;
+    iconv_with_policy(input, from_encoding, to_encoding, OnError::Strict)
+}
+
+/// convert `input` from `from_encoding` to `to_encoding`, handling unconvertible
+/// characters according to `policy` instead of failing on the first one
+pub fn iconv_with_policy(
+    input: &[u8],
+    from_encoding: &str,
+    to_encoding: &str,
+    policy: OnError,
+) -> Result<Vec<u8>, IconvError> {
+    let mut c = Iconv::with_policy(from_encoding, to_encoding, policy)?;
+    convert_all(&mut c, input)
+}
+
+fn convert_all(c: &mut Iconv, input: &[u8]) -> Result<Vec<u8>, IconvError> {
     let mut read = 0;
     let mut output = VecBuf::new(MIN_WRITE);
     loop {
@@ -107,6 +172,27 @@ pub fn iconv(input: &[u8], from_encoding: &str, to_encoding: &str) -> Result<Vec
                 read += r;
                 output.grow(0);
             }
+            // glibc's `//TRANSLIT`/`//IGNORE` report a transliteration or a drop
+            // that actually succeeded as `-1`/`EILSEQ` once some input was
+            // consumed; treat that as ordinary progress rather than an error.
+            Err((r, w, IconvError::InvalidInput)) if c.lenient && r > 0 => {
+                output.commit(w);
+                read += r;
+                if read >= input.len() {
+                    return Ok(output.into_vec());
+                }
+            }
+            Err((r, w, IconvError::InvalidInput | IconvError::IncompleteInput))
+                if c.replacement.is_some() =>
+            {
+                output.commit(w);
+                read += r;
+                output.write_all(c.replacement.as_ref().unwrap());
+                if read >= input.len() {
+                    return Ok(output.into_vec());
+                }
+                read += 1;
+            }
             Err((_, _, e)) => return Err(e),
         }
     }
@@ -117,17 +203,42 @@ pub fn encode(input: &str, encoding: &str) -> Result<Vec<u8>, IconvError> {
     iconv(input.as_bytes(), "UTF-8", encoding)
 }
 
+/// convert `input` from UTF-8 to `encoding`, handling unconvertible characters
+/// according to `policy` instead of failing on the first one
+pub fn encode_with_policy(input: &str, encoding: &str, policy: OnError) -> Result<Vec<u8>, IconvError> {
+    iconv_with_policy(input.as_bytes(), "UTF-8", encoding, policy)
+}
+
 /// convert `input` from `encoding` to UTF-8
 pub fn decode(input: &[u8], encoding: &str) -> Result<String, IconvError> {
     iconv(input, encoding, "UTF-8").map(|v| unsafe { String::from_utf8_unchecked(v) })
 }
 
+/// convert `input` from `encoding` to UTF-8, handling unconvertible characters
+/// according to `policy` instead of failing on the first one
+pub fn decode_with_policy(
+    input: &[u8],
+    encoding: &str,
+    policy: OnError,
+) -> Result<String, IconvError> {
+    iconv_with_policy(input, encoding, "UTF-8", policy).map(|v| unsafe { String::from_utf8_unchecked(v) })
+}
+
+/// convert `input` from `encoding` to UTF-8, substituting U+FFFD (the Unicode
+/// replacement character) for any invalid or incomplete byte sequence.
+///
+/// "Lossy" only covers malformed bytes in `input`; an unknown or unsupported
+/// `encoding` still fails, so this returns a `Result` rather than a bare `String`.
+pub fn decode_lossy(input: &[u8], encoding: &str) -> Result<String, IconvError> {
+    decode_with_policy(input, encoding, OnError::Replace('\u{FFFD}'))
+}
+
 pub fn copy<R: Read, W: Write>(
     input: R,
     mut output: W,
     from_encoding: &str,
     to_encoding: &str,
-) -> std::io::Result<usize> {
+) -> io::Result<usize> {
     let mut cr =
         IconvReader::new(input, from_encoding, to_encoding).map_err(|e| e.into_io_error())?;
     let mut w = 0;
@@ -146,25 +257,68 @@ pub fn copy<R: Read, W: Write>(
 impl Iconv {
     /// Creates a new Converter from `from_encoding` to `to_encoding`.
     pub fn new(from_encoding: &str, to_encoding: &str) -> Result<Iconv, IconvError> {
+        Self::with_policy(from_encoding, to_encoding, OnError::Strict)
+    }
+
+    /// Creates a new Converter from `from_encoding` to `to_encoding` that applies
+    /// `policy` to characters that cannot be represented in `to_encoding`.
+    ///
+    /// `Transliterate` and `Ignore` are delegated to the underlying iconv
+    /// implementation via the `//TRANSLIT` and `//IGNORE` target suffixes.
+    /// `Replace` is handled by the higher-level [`iconv_with_policy`] conversion
+    /// loop, which substitutes the given character and skips forward on error.
+    pub fn with_policy(
+        from_encoding: &str,
+        to_encoding: &str,
+        policy: OnError,
+    ) -> Result<Iconv, IconvError> {
+        let suffix = match policy {
+            OnError::Transliterate => "//TRANSLIT",
+            OnError::Ignore => "//IGNORE",
+            OnError::Strict | OnError::Replace(_) => "",
+        };
+        let lenient = matches!(policy, OnError::Transliterate | OnError::Ignore);
+        let mut target = String::from(to_encoding);
+        target.push_str(suffix);
+
+        let cd = Self::open(from_encoding, &target)?;
+        let mut conv = Iconv {
+            cd,
+            replacement: None,
+            lenient,
+        };
+        if let OnError::Replace(ch) = policy {
+            let mut buf = [0u8; 4];
+            let replacement = iconv(ch.encode_utf8(&mut buf).as_bytes(), "UTF-8", to_encoding)?;
+            conv.replacement = Some(replacement);
+        }
+        Ok(conv)
+    }
+
+    fn open(from_encoding: &str, to_encoding: &str) -> Result<ffi::iconv_t, IconvError> {
+        #[cfg(not(feature = "core_io"))]
         use std::ffi::CString;
+        #[cfg(feature = "core_io")]
+        use alloc::ffi::CString;
+
         let from_code = CString::new(from_encoding).unwrap();
         let to_code = CString::new(to_encoding).unwrap();
 
         let handle = unsafe { ffi::iconv_open(to_code.as_ptr(), from_code.as_ptr()) };
         if handle as isize == -1 {
-            let e = std::io::Error::last_os_error().raw_os_error().unwrap();
+            let e = last_errno();
             return Err(if e == libc::EINVAL {
                 IconvError::ConversionNotSupport
             } else {
                 IconvError::OsError(e)
             });
         }
-        Ok(Iconv { cd: handle })
+        Ok(handle)
     }
 
     /// reset to the initial state
     pub fn reset(&mut self) {
-        use std::ptr::null_mut;
+        use core::ptr::null_mut;
         unsafe { ffi::iconv(self.cd, null_mut(), null_mut(), null_mut(), null_mut()) };
     }
 
@@ -190,7 +344,7 @@ impl Iconv {
             ffi::iconv(
                 self.cd,
                 if input.is_empty() {
-                    std::ptr::null_mut()
+                    core::ptr::null_mut()
                 } else {
                     input_ptr_ptr
                 },
@@ -205,7 +359,7 @@ impl Iconv {
         if chars as isize != -1 {
             Ok((bytes_read, bytes_written, chars as usize))
         } else {
-            let errno = std::io::Error::last_os_error().raw_os_error().unwrap();
+            let errno = last_errno();
             Err((
                 bytes_read,
                 bytes_written,
@@ -273,7 +427,7 @@ impl<W: Write> IconvWriter<W> {
 }
 
 impl<R: Read> Read for IconvReader<R> {
-    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         let mut wrote = 0;
         loop {
             let n = self.reader.read(self.input.prepare_at_least(0))?;
@@ -309,15 +463,73 @@ impl<R: Read> Read for IconvReader<R> {
             }
         }
     }
+
+    #[cfg(not(feature = "core_io"))]
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        let mut wrote = 0;
+        for buf in bufs.iter_mut() {
+            if buf.is_empty() {
+                continue;
+            }
+            loop {
+                let n = self.reader.read(self.input.prepare_at_least(0))?;
+                self.input.commit(n);
+
+                match self.iconv.convert(self.input.data(), &mut buf[..]) {
+                    Ok((r, w, _)) => {
+                        self.input.consume(r);
+                        wrote += w;
+                        return Ok(wrote);
+                    }
+                    Err((r, w, IconvError::NotSufficientOutput)) => {
+                        self.input.consume(r);
+                        wrote += w;
+                        if w == 0 || w == buf.len() {
+                            // Either nothing landed in `buf` (it stays untouched, so
+                            // skipping it leaves no gap) or `buf` is fully packed (safe
+                            // to move on). Either way the next slice starts flush with
+                            // the reported total.
+                            break;
+                        }
+                        // `buf` has leftover room that the next char didn't fit in. Moving
+                        // on to the next slice here would make `wrote` claim bytes past an
+                        // unwritten hole in this one, breaking the contiguous-fill contract
+                        // of `Read::read_vectored`. Stop and report what's genuinely filled.
+                        return Ok(wrote);
+                    }
+                    Err((r, w, e @ IconvError::IncompleteInput)) => {
+                        self.input.consume(r);
+                        wrote += w;
+                        if n == 0 {
+                            return if wrote > 0 {
+                                Ok(wrote)
+                            } else {
+                                Err(e.into_io_error())
+                            };
+                        }
+                        // more input may complete the pending sequence; keep pulling
+                    }
+                    Err((_, _, e)) => {
+                        return if wrote > 0 {
+                            Ok(wrote)
+                        } else {
+                            Err(e.into_io_error())
+                        };
+                    }
+                }
+            }
+        }
+        Ok(wrote)
+    }
 }
 
 impl<R: Read> BufRead for IconvReader<R> {
-    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
         if self.output.is_empty() {
-            let mut o = std::mem::take(&mut self.output);
+            let mut o = core::mem::take(&mut self.output);
             let n = self.read(o.prepare_at_least(0))?;
             o.commit(n);
-            let _ = std::mem::replace(&mut self.output, o);
+            let _ = core::mem::replace(&mut self.output, o);
         }
         Ok(self.output.data())
     }
@@ -328,7 +540,7 @@ impl<R: Read> BufRead for IconvReader<R> {
 }
 
 impl<W: Write> Write for IconvWriter<W> {
-    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         if self.input.is_empty() {
             match self.iconv.convert(buf, self.output.prepare_at_least(0)) {
                 Ok((r, w, _)) | Err((r, w, IconvError::IncompleteInput)) => {
@@ -362,7 +574,7 @@ impl<W: Write> Write for IconvWriter<W> {
         }
     }
 
-    fn flush(&mut self) -> std::io::Result<()> {
+    fn flush(&mut self) -> io::Result<()> {
         let _ = self.write(&[])?;
 
         if !self.input.is_empty() {
@@ -375,20 +587,33 @@ impl<W: Write> Write for IconvWriter<W> {
         self.writer.flush()
     }
 
-    fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
         let w = self.write(buf)?;
         if w < buf.len() {
             self.input.write_all(&buf[w..]);
         }
         Ok(())
     }
+
+    #[cfg(not(feature = "core_io"))]
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        let mut consumed = 0;
+        for buf in bufs {
+            if buf.is_empty() {
+                continue;
+            }
+            self.write_all(buf)?;
+            consumed += buf.len();
+        }
+        Ok(consumed)
+    }
 }
 
-#[cfg(test)]
+#[cfg(all(test, not(feature = "core_io")))]
 mod test {
     use std::{
         io,
-        io::{BufReader, Read},
+        io::{BufReader, IoSlice, IoSliceMut, Read},
         iter,
     };
 
@@ -419,7 +644,7 @@ mod test {
             let res = cr.read(&mut buf[..k]);
             println!("{:?}", res);
             match res {
-                Ok(n) if n == 0 => {
+                Ok(0) => {
                     assert_eq!(nread, gbk.len());
                     return;
                 }
@@ -509,6 +734,63 @@ mod test {
         assert_eq!(&writer.into_inner(), &gbk);
     }
 
+    #[test]
+    fn test_reader_read_vectored() {
+        let a = "噗哈";
+        let a_gbk = [224u8, 219, 185, 254];
+        let mut input = String::new();
+        let mut gbk: Vec<u8> = Vec::new();
+        for i in 0..1024 {
+            let i = i.to_string();
+            input.push_str(&i);
+            input.push_str(a);
+            gbk.extend(i.as_bytes());
+            gbk.extend(a_gbk);
+        }
+
+        let r = BufReader::new(input.as_bytes());
+        let mut cr = IconvReader::new(r, "UTF-8", "GBK").unwrap();
+
+        let mut nread = 0;
+        loop {
+            let mut a = [0u8; 3];
+            let mut b = [0u8; 5];
+            let n = {
+                let mut bufs = [IoSliceMut::new(&mut a), IoSliceMut::new(&mut b)];
+                cr.read_vectored(&mut bufs).unwrap()
+            };
+            if n == 0 {
+                assert_eq!(nread, gbk.len());
+                return;
+            }
+            let mut got = Vec::new();
+            got.extend_from_slice(&a[..n.min(3)]);
+            if n > 3 {
+                got.extend_from_slice(&b[..n - 3]);
+            }
+            assert_eq!(got, gbk[nread..nread + n]);
+            nread += n;
+        }
+    }
+
+    #[test]
+    fn test_writer_write_vectored() {
+        let a = "噗哈";
+        let a_gbk = [224u8, 219, 185, 254];
+        let mut writer = IconvWriter::new(vec![], "UTF-8", "GBK").unwrap();
+        let mut gbk: Vec<u8> = Vec::new();
+        for i in 0..1024 {
+            let i = i.to_string();
+            let bufs = [IoSlice::new(i.as_bytes()), IoSlice::new(a.as_bytes())];
+            let n = writer.write_vectored(&bufs).unwrap();
+            assert_eq!(n, i.len() + a.len());
+            gbk.extend(i.as_bytes());
+            gbk.extend(a_gbk);
+        }
+
+        assert_eq!(&writer.into_inner(), &gbk);
+    }
+
     #[test]
     fn test_encoder_normal() {
         assert!(encode("", "LATIN1").unwrap().is_empty());
@@ -516,7 +798,7 @@ mod test {
         let a = "哈哈";
         assert_eq!(encode(a, "GBK").unwrap(), vec!(0xb9, 0xfe, 0xb9, 0xfe));
 
-        let b = iter::repeat(a).take(1024).collect::<Vec<&str>>().join("");
+        let b = iter::repeat_n(a, 1024).collect::<Vec<&str>>().join("");
 
         for ch in encode(&b, "GBK").unwrap().chunks(4) {
             assert_eq!(ch, &vec![0xb9, 0xfe, 0xb9, 0xfe][..]);
@@ -596,4 +878,50 @@ mod test {
         let b = "变巨";
         assert_eq!(encode(a, "BIG5").unwrap(), encode(b, "GBK").unwrap());
     }
+
+    #[test]
+    fn test_decode_lossy_replaces_invalid_bytes() {
+        let a = vec![0xb9, 0xfe, 0xff, 0xff, 0xb9, 0xfe]; // "哈" + two invalid bytes + "哈"
+        // each invalid byte is skipped and replaced on its own, one U+FFFD per byte
+        assert_eq!(decode_lossy(&a, "GBK").unwrap(), "哈\u{FFFD}\u{FFFD}哈".to_string());
+    }
+
+    #[test]
+    fn test_decode_lossy_replaces_trailing_incomplete_sequence() {
+        let a = vec![0xb9, 0xfe, 0xb9]; // "哈" + incomplete gbk bytes
+        assert_eq!(decode_lossy(&a, "GBK").unwrap(), "哈\u{FFFD}".to_string());
+    }
+
+    #[test]
+    fn test_decode_lossy_rejects_unknown_encoding() {
+        assert!(decode_lossy(b"x", "NOT_EXISTS").is_err());
+    }
+
+    #[test]
+    fn test_encode_with_policy_transliterate() {
+        // glibc's ASCII//TRANSLIT charmap doesn't know U+00E9, so it falls back
+        // to its generic untransliterable-character marker, "?"
+        let a = "caf\u{00e9}";
+        assert_eq!(
+            encode_with_policy(a, "ASCII", OnError::Transliterate).unwrap(),
+            b"caf?"
+        );
+    }
+
+    #[test]
+    fn test_encode_with_policy_ignore() {
+        let a = "caf\u{00e9}";
+        assert_eq!(
+            encode_with_policy(a, "ASCII", OnError::Ignore).unwrap(),
+            b"caf"
+        );
+    }
+
+    #[test]
+    fn test_encode_with_policy_strict_matches_encode() {
+        assert_eq!(
+            encode_with_policy("哈哈", "GBK", OnError::Strict).unwrap(),
+            encode("哈哈", "GBK").unwrap()
+        );
+    }
 }